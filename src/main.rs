@@ -1,22 +1,71 @@
 use handlebars::Handlebars;
 use serde::{Deserialize, Serialize};
 use sqlite::Connection;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use warp::Filter;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 struct Twit {
     id: Option<i64>,
     user: String,
     content: String,
     created_at: Option<i64>,
+    timeline_id: i64,
 }
 
+/// The lifecycle of an invitation to join a timeline: `Sent` until the
+/// invitee accepts it (`Accepted`) or an admin revokes it (`Revoked`).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+enum InvitationState {
+    Sent,
+    Accepted,
+    Revoked,
+}
+
+impl InvitationState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            InvitationState::Sent => "Sent",
+            InvitationState::Accepted => "Accepted",
+            InvitationState::Revoked => "Revoked",
+        }
+    }
+
+    fn from_str(s: &str) -> Self {
+        match s {
+            "Accepted" => InvitationState::Accepted,
+            "Revoked" => InvitationState::Revoked,
+            _ => InvitationState::Sent,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Invitation {
+    id: i64,
+    email: String,
+    timeline_id: i64,
+    invited_by: String,
+    state: InvitationState,
+}
+
+/// Maps a session token (the value of the `session` cookie) to the username
+/// that logged in to create it.
+type Sessions = Arc<Mutex<HashMap<String, String>>>;
+
 struct TwitterServer {
     db: Arc<Mutex<Connection>>,
     hbs: Arc<Handlebars<'static>>,
+    twit_tx: tokio::sync::broadcast::Sender<Twit>,
+    sessions: Sessions,
+    nostr_relays: nostr_bridge::RelayConnections,
 }
 
+/// Largest page of twits a single request may return, regardless of the
+/// requested `limit`, so a search can't be used to dump the whole table.
+const MAX_PAGE_LIMIT: i64 = 50;
+
 mod filters {
     use crate::Twit;
 
@@ -25,44 +74,329 @@ mod filters {
     use serde::Deserialize;
     use sqlite::Connection;
     use std::sync::{Arc, Mutex};
+    use tokio::sync::broadcast;
     use warp::Filter;
 
+    #[derive(Deserialize)]
+    pub struct PageQuery {
+        pub q: Option<String>,
+        pub limit: Option<i64>,
+        pub cursor: Option<i64>,
+    }
+
     pub fn twits(
         db: Arc<Mutex<Connection>>,
+        twit_tx: broadcast::Sender<Twit>,
+        sessions: crate::Sessions,
+        nostr_relays: crate::nostr_bridge::RelayConnections,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        stream_twits(twit_tx.clone())
+            .or(search_twits(db.clone(), sessions.clone()))
+            .or(list_twits(db.clone(), sessions.clone()))
+            .or(create_twit(
+                db.clone(),
+                twit_tx.clone(),
+                sessions.clone(),
+                nostr_relays.clone(),
+            ))
+            .or(create_timeline(db.clone(), sessions.clone()))
+            .or(invite(db.clone(), sessions.clone()))
+            .or(accept_invitation(db.clone(), sessions.clone()))
+            .or(register(db.clone()))
+            .or(login(db.clone(), sessions))
+            .or(outbox(db.clone()))
+            .or(inbox(db.clone()))
+            .or(nostr_info(db.clone()))
+            .or(micropub(db.clone(), twit_tx, nostr_relays))
+            .or(actor(db))
+    }
+
+    /// `POST /timelines` — create a timeline, with the authenticated user as
+    /// its first member.
+    pub fn create_timeline(
+        db: Arc<Mutex<Connection>>,
+        sessions: crate::Sessions,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        #[derive(Deserialize)]
+        struct NewTimeline {
+            name: String,
+        }
+        warp::path!("timelines")
+            .and(warp::post())
+            .and(warp::cookie::optional::<String>("session"))
+            .and(warp::any().map(move || sessions.clone()))
+            .and_then(handlers::authenticate)
+            .and(warp::body::form::<NewTimeline>())
+            .map(|user: String, nt: NewTimeline| (user, nt.name))
+            .untuple_one()
+            .and(warp::any().map(move || db.clone()))
+            .and_then(handlers::create_timeline)
+    }
+
+    /// `POST /timelines/<id>/invite` — a member invites an email address,
+    /// getting back a single-use acceptance token.
+    pub fn invite(
+        db: Arc<Mutex<Connection>>,
+        sessions: crate::Sessions,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        #[derive(Deserialize)]
+        struct InviteForm {
+            email: String,
+        }
+        warp::path!("timelines" / i64 / "invite")
+            .and(warp::post())
+            .and(warp::cookie::optional::<String>("session"))
+            .and(warp::any().map(move || sessions.clone()))
+            .and_then(|timeline_id: i64, session, sessions| async move {
+                let user = handlers::authenticate(session, sessions).await?;
+                Ok::<_, warp::Rejection>((timeline_id, user))
+            })
+            .untuple_one()
+            .and(warp::body::form::<InviteForm>())
+            .map(|timeline_id: i64, inviter: String, form: InviteForm| {
+                (timeline_id, inviter, form.email)
+            })
+            .untuple_one()
+            .and(warp::any().map(move || db.clone()))
+            .and_then(handlers::invite)
+    }
+
+    /// `POST /invitations/<token>/accept` — the logged-in invitee redeems
+    /// their invitation and becomes a member.
+    pub fn accept_invitation(
+        db: Arc<Mutex<Connection>>,
+        sessions: crate::Sessions,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("invitations" / String / "accept")
+            .and(warp::post())
+            .and(warp::cookie::optional::<String>("session"))
+            .and(warp::any().map(move || sessions.clone()))
+            .and_then(|token: String, session, sessions| async move {
+                let user = handlers::authenticate(session, sessions).await?;
+                Ok::<_, warp::Rejection>((token, user))
+            })
+            .untuple_one()
+            .and(warp::any().map(move || db.clone()))
+            .and_then(handlers::accept_invitation)
+    }
+
+    /// `GET /nostr/<name>` — the user's npub and the ids of kind-1 events
+    /// published on their behalf so far.
+    pub fn nostr_info(
+        db: Arc<Mutex<Connection>>,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("nostr" / String)
+            .and(warp::get())
+            .and(warp::any().map(move || db.clone()))
+            .and_then(handlers::nostr_info)
+    }
+
+    #[derive(Deserialize)]
+    pub struct MicropubForm {
+        pub h: String,
+        pub content: String,
+    }
+
+    #[derive(Deserialize)]
+    pub struct MicropubQuery {
+        pub q: Option<String>,
+    }
+
+    /// IndieAuth + Micropub entry point: `GET /micropub?q=config` returns a
+    /// config document, `POST /micropub` creates a twit on behalf of a
+    /// bearer-token-verified IndieAuth identity.
+    pub fn micropub(
+        db: Arc<Mutex<Connection>>,
+        twit_tx: broadcast::Sender<Twit>,
+        nostr_relays: crate::nostr_bridge::RelayConnections,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        micropub_config().or(micropub_post(db, twit_tx, nostr_relays))
+    }
+
+    fn micropub_config(
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("micropub")
+            .and(warp::get())
+            .and(warp::query::<MicropubQuery>())
+            .and_then(|query: MicropubQuery| async move {
+                if query.q.as_deref() == Some("config") {
+                    Ok(warp::reply::json(&serde_json::json!({})))
+                } else {
+                    Err(warp::reject::not_found())
+                }
+            })
+    }
+
+    fn micropub_post(
+        db: Arc<Mutex<Connection>>,
+        twit_tx: broadcast::Sender<Twit>,
+        nostr_relays: crate::nostr_bridge::RelayConnections,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("micropub")
+            .and(warp::post())
+            .and(warp::header::<String>("authorization"))
+            .and(warp::body::form::<MicropubForm>())
+            .and(warp::any().map(move || db.clone()))
+            .and(warp::any().map(move || twit_tx.clone()))
+            .and(warp::any().map(move || nostr_relays.clone()))
+            .and_then(handlers::micropub_post)
+    }
+
+    /// `GET /users/<name>` — the ActivityPub actor document for a local user.
+    pub fn actor(
+        db: Arc<Mutex<Connection>>,
     ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
-        list_twits(db.clone()).or(create_twit(db.clone()))
+        warp::path!("users" / String)
+            .and(warp::get())
+            .and(warp::any().map(move || db.clone()))
+            .and_then(handlers::actor)
+    }
+
+    /// `GET /users/<name>/outbox` — the user's newest twits on the public
+    /// default timeline (at most `MAX_PAGE_LIMIT`) rendered as `Create`/`Note`
+    /// activities; twits on other timelines are not listed.
+    pub fn outbox(
+        db: Arc<Mutex<Connection>>,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("users" / String / "outbox")
+            .and(warp::get())
+            .and(warp::any().map(move || db.clone()))
+            .and_then(handlers::outbox)
+    }
+
+    /// `POST /users/<name>/inbox` — accepts `Follow` activities from remote
+    /// actors, verifying the request's HTTP Signature against the sending
+    /// actor's public key before replying with `Accept`.
+    pub fn inbox(
+        db: Arc<Mutex<Connection>>,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("users" / String / "inbox")
+            .and(warp::post())
+            .and(warp::header::optional::<String>("signature"))
+            .and(warp::header::optional::<String>("host"))
+            .and(warp::header::optional::<String>("date"))
+            .and(warp::header::optional::<String>("digest"))
+            .and(warp::body::bytes())
+            .and(warp::any().map(move || db.clone()))
+            .and_then(handlers::inbox)
+    }
+
+    /// Resolves the session cookie to a username and confirms they're a
+    /// member of `timeline_id`, shared by every `/timelines/<id>/twits` route
+    /// so only members can read or post. Yields `(timeline_id, username)`.
+    fn require_member(
+        db: Arc<Mutex<Connection>>,
+        sessions: crate::Sessions,
+    ) -> impl Filter<Extract = (i64, String), Error = warp::Rejection> + Clone {
+        warp::path!("timelines" / i64 / "twits")
+            .and(warp::cookie::optional::<String>("session"))
+            .and(warp::any().map(move || sessions.clone()))
+            .and_then(|timeline_id: i64, session, sessions| async move {
+                let user = handlers::authenticate(session, sessions).await?;
+                Ok::<_, warp::Rejection>((timeline_id, user))
+            })
+            .untuple_one()
+            .and(warp::any().map(move || db.clone()))
+            .and_then(handlers::require_member)
+            .untuple_one()
     }
 
     pub fn list_twits(
         db: Arc<Mutex<Connection>>,
+        sessions: crate::Sessions,
     ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
-        warp::path!("twits")
+        require_member(db.clone(), sessions)
             .and(warp::get())
+            .and(warp::query::<PageQuery>())
             .and(warp::any().map(move || db.clone()))
             .and_then(handlers::list_twits)
     }
 
+    pub fn search_twits(
+        db: Arc<Mutex<Connection>>,
+        sessions: crate::Sessions,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        require_member(db.clone(), sessions)
+            .and(warp::get())
+            .and(warp::query::<PageQuery>())
+            .and_then(|timeline_id: i64, _user: String, query: PageQuery| async move {
+                if query.q.as_deref().unwrap_or("").is_empty() {
+                    Err(warp::reject::not_found())
+                } else {
+                    Ok((timeline_id, query))
+                }
+            })
+            .untuple_one()
+            .and(warp::any().map(move || db.clone()))
+            .and_then(handlers::search_twits)
+    }
+
     pub fn create_twit(
         db: Arc<Mutex<Connection>>,
+        twit_tx: broadcast::Sender<Twit>,
+        sessions: crate::Sessions,
+        nostr_relays: crate::nostr_bridge::RelayConnections,
     ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
         #[derive(Deserialize)]
         struct PartialTwit {
             content: String,
         }
-        warp::path!("twits")
+        require_member(db.clone(), sessions)
             .and(warp::post())
-            .and(warp::addr::remote())
             .and(warp::body::form::<PartialTwit>())
-            .map(|ip: Option<std::net::SocketAddr>, pt: PartialTwit| Twit {
-                id: None,
-                user: ip.unwrap().ip().to_string(),
-                content: pt.content,
-                created_at: None,
-            })
+            .map(
+                |timeline_id: i64, user: String, pt: PartialTwit| Twit {
+                    id: None,
+                    user,
+                    content: pt.content,
+                    created_at: None,
+                    timeline_id,
+                },
+            )
             .and(warp::any().map(move || db.clone()))
+            .and(warp::any().map(move || twit_tx.clone()))
+            .and(warp::any().map(move || nostr_relays.clone()))
             .and_then(handlers::create_twit)
     }
 
+    /// `POST /register` — create a user with an Argon2id-hashed password.
+    pub fn register(
+        db: Arc<Mutex<Connection>>,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("register")
+            .and(warp::post())
+            .and(warp::body::form::<handlers::Credentials>())
+            .and(warp::any().map(move || db.clone()))
+            .and_then(handlers::register)
+    }
+
+    /// `POST /login` — verify a password and issue a session cookie.
+    pub fn login(
+        db: Arc<Mutex<Connection>>,
+        sessions: crate::Sessions,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("login")
+            .and(warp::post())
+            .and(warp::body::form::<handlers::Credentials>())
+            .and(warp::any().map(move || db.clone()))
+            .and(warp::any().map(move || sessions.clone()))
+            .and_then(handlers::login)
+    }
+
+    /// `GET /twits/stream` — a keep-alive SSE feed of twits newly created on
+    /// the public default timeline (twits on other timelines are not sent),
+    /// so clients can watch the timeline update without re-polling `twits_html`.
+    pub fn stream_twits(
+        twit_tx: broadcast::Sender<Twit>,
+    ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        warp::path!("twits" / "stream")
+            .and(warp::get())
+            .map(move || {
+                let rx = twit_tx.subscribe();
+                warp::sse::reply(warp::sse::keep_alive().stream(handlers::twit_events(rx)))
+            })
+    }
+
     pub fn html(
         db: Arc<Mutex<Connection>>,
         hbs: Arc<Handlebars<'static>>,
@@ -82,25 +416,53 @@ mod filters {
     ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
         warp::path!("twits_html")
             .and(warp::get())
-            .and_then(move || handlers::list_twit_html(db.clone(), hbs.clone()))
+            .and(warp::query::<PageQuery>())
+            .and_then(move |query: PageQuery| handlers::list_twit_html(query, db.clone(), hbs.clone()))
     }
 }
 
 mod handlers {
+    use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+    use argon2::Argon2;
     use handlebars::Handlebars;
-    use serde::Serialize;
+    use rand::rngs::OsRng;
+    use rand::Rng;
+    use serde::{Deserialize, Serialize};
     use sqlite::{Connection, State};
     use std::{
         convert::Infallible,
         sync::{Arc, Mutex},
     };
+    use warp::http::StatusCode;
 
-    use crate::Twit;
+    use crate::filters::PageQuery;
+    use crate::{Invitation, InvitationState, Sessions, Twit, MAX_PAGE_LIMIT};
+
+    #[derive(Deserialize)]
+    pub struct Credentials {
+        pub username: String,
+        pub password: String,
+    }
 
-    pub async fn list_twits_from_db(db: Arc<Mutex<Connection>>) -> Vec<Twit> {
+    /// A page of twits, optionally filtered by a search term, walked backwards
+    /// from `cursor` (exclusive) by `id` so callers can keep paging with the
+    /// `id` of the last row they saw.
+    pub async fn list_twits_from_db(
+        db: Arc<Mutex<Connection>>,
+        timeline_id: i64,
+        term: Option<&str>,
+        cursor: i64,
+        limit: i64,
+    ) -> Vec<Twit> {
         let conn = db.lock().unwrap();
-        let query = "SELECT * FROM twits";
+        let query = "SELECT * FROM twits
+                     WHERE timeline_id = ? AND content LIKE '%'||?||'%' AND id < ?
+                     ORDER BY id DESC LIMIT ?";
         let mut stmt = conn.prepare(query).unwrap();
+        stmt.bind((1, timeline_id)).unwrap();
+        stmt.bind((2, term.unwrap_or(""))).unwrap();
+        stmt.bind((3, cursor)).unwrap();
+        stmt.bind((4, limit)).unwrap();
 
         let mut twits = Vec::new();
         while let Ok(State::Row) = stmt.next() {
@@ -109,6 +471,7 @@ mod handlers {
                 user: stmt.read::<String, _>("user").unwrap(),
                 content: stmt.read::<String, _>("content").unwrap(),
                 created_at: Some(stmt.read::<i64, _>("createdAt").unwrap()),
+                timeline_id: stmt.read::<i64, _>("timeline_id").unwrap(),
             };
             twits.push(t);
         }
@@ -116,41 +479,692 @@ mod handlers {
         twits
     }
 
-    pub async fn list_twits(db: Arc<Mutex<Connection>>) -> Result<impl warp::Reply, Infallible> {
-        let twits = list_twits_from_db(db).await;
-        Ok(warp::reply::json(&twits))
+    #[derive(Serialize)]
+    pub struct TwitPage {
+        pub twits: Vec<Twit>,
+        pub next_cursor: Option<i64>,
     }
 
+    fn clamp_limit(limit: Option<i64>) -> i64 {
+        limit.unwrap_or(MAX_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT)
+    }
+
+    async fn page_twits(db: Arc<Mutex<Connection>>, timeline_id: i64, query: PageQuery) -> TwitPage {
+        let limit = clamp_limit(query.limit);
+        let cursor = query.cursor.unwrap_or(i64::MAX);
+        let twits = list_twits_from_db(db, timeline_id, query.q.as_deref(), cursor, limit).await;
+        let next_cursor = if twits.len() as i64 == limit {
+            twits.last().and_then(|t| t.id)
+        } else {
+            None
+        };
+        TwitPage { twits, next_cursor }
+    }
+
+    pub async fn list_twits(
+        timeline_id: i64,
+        _user: String,
+        query: PageQuery,
+        db: Arc<Mutex<Connection>>,
+    ) -> Result<impl warp::Reply, Infallible> {
+        let page = page_twits(db, timeline_id, query).await;
+        Ok(warp::reply::json(&page))
+    }
+
+    pub async fn search_twits(
+        timeline_id: i64,
+        query: PageQuery,
+        db: Arc<Mutex<Connection>>,
+    ) -> Result<impl warp::Reply, Infallible> {
+        let page = page_twits(db, timeline_id, query).await;
+        Ok(warp::reply::json(&page))
+    }
+
+    /// The default timeline seeded at startup, used by the legacy
+    /// `/twits_html` view so it keeps working without a timeline segment.
+    const DEFAULT_TIMELINE_ID: i64 = 1;
+
     pub async fn list_twit_html(
+        query: PageQuery,
         db: Arc<Mutex<Connection>>,
         hbs: Arc<Handlebars<'static>>,
     ) -> Result<impl warp::Reply, Infallible> {
-        let twits = list_twits_from_db(db).await;
+        let page = page_twits(db, DEFAULT_TIMELINE_ID, query).await;
 
         #[derive(Serialize)]
         struct TwitList {
             twits: Vec<Twit>,
+            next_cursor: Option<i64>,
         }
-        let twit_obj = TwitList { twits };
+        let twit_obj = TwitList {
+            twits: page.twits,
+            next_cursor: page.next_cursor,
+        };
 
         let rendered = hbs.render("twits_html", &twit_obj).unwrap();
 
         Ok(warp::reply::html(rendered))
     }
 
+    #[derive(Serialize)]
+    struct RegisterResponse {
+        username: String,
+    }
+
+    pub async fn register(
+        creds: Credentials,
+        db: Arc<Mutex<Connection>>,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let salt = SaltString::generate(&mut OsRng);
+        let password_hash = Argon2::default()
+            .hash_password(creds.password.as_bytes(), &salt)
+            .map_err(|_| warp::reject::custom(AuthError))?
+            .to_string();
+        let (private_key_pem, public_key_pem) = crate::federation::generate_keypair();
+        let nostr_secret_key = crate::nostr_bridge::generate_keys()
+            .secret_key()
+            .unwrap()
+            .to_string();
+
+        let conn = db.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "INSERT INTO users
+                    (username, password_hash, public_key_pem, private_key_pem, nostr_secret_key)
+                 VALUES (?, ?, ?, ?, ?);",
+            )
+            .unwrap();
+        stmt.bind((1, creds.username.as_str())).unwrap();
+        stmt.bind((2, password_hash.as_str())).unwrap();
+        stmt.bind((3, public_key_pem.as_str())).unwrap();
+        stmt.bind((4, private_key_pem.as_str())).unwrap();
+        stmt.bind((5, nostr_secret_key.as_str())).unwrap();
+        if stmt.next().is_err() {
+            return Err(warp::reject::custom(AuthError));
+        }
+
+        Ok(warp::reply::with_status(
+            warp::reply::json(&RegisterResponse {
+                username: creds.username,
+            }),
+            StatusCode::CREATED,
+        ))
+    }
+
+    pub async fn login(
+        creds: Credentials,
+        db: Arc<Mutex<Connection>>,
+        sessions: Sessions,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let stored_hash = {
+            let conn = db.lock().unwrap();
+            let mut stmt = conn
+                .prepare("SELECT password_hash FROM users WHERE username = ?")
+                .unwrap();
+            stmt.bind((1, creds.username.as_str())).unwrap();
+            if !matches!(stmt.next(), Ok(State::Row)) {
+                return Err(warp::reject::custom(AuthError));
+            }
+            stmt.read::<String, _>("password_hash").unwrap()
+        };
+
+        let parsed_hash = PasswordHash::new(&stored_hash).map_err(|_| warp::reject::custom(AuthError))?;
+        Argon2::default()
+            .verify_password(creds.password.as_bytes(), &parsed_hash)
+            .map_err(|_| warp::reject::custom(AuthError))?;
+
+        let token: String = {
+            let mut rng = OsRng;
+            (0..32).map(|_| format!("{:02x}", rng.gen::<u8>())).collect()
+        };
+        sessions
+            .lock()
+            .unwrap()
+            .insert(token.clone(), creds.username);
+
+        let cookie = format!("session={}; HttpOnly; Path=/", token);
+        Ok(warp::reply::with_header(
+            warp::reply::with_status(warp::reply(), StatusCode::OK),
+            "Set-Cookie",
+            cookie,
+        ))
+    }
+
+    /// Resolves the authoring user from the `session` cookie, rejecting the
+    /// request when there is no matching session.
+    pub async fn authenticate(
+        session: Option<String>,
+        sessions: Sessions,
+    ) -> Result<String, warp::Rejection> {
+        session
+            .and_then(|token| sessions.lock().unwrap().get(&token).cloned())
+            .ok_or_else(|| warp::reject::custom(AuthError))
+    }
+
+    #[derive(Debug)]
+    pub struct AuthError;
+    impl warp::reject::Reject for AuthError {}
+
+    /// Maps `AuthError` to a 401 response so failed logins, missing
+    /// sessions, non-member timeline access, and rejected Micropub tokens
+    /// come back as "unauthorized" rather than falling through to warp's
+    /// default 500.
+    pub async fn handle_rejection(
+        err: warp::Rejection,
+    ) -> Result<impl warp::Reply, Infallible> {
+        if err.find::<AuthError>().is_some() {
+            return Ok(warp::reply::with_status(
+                "Unauthorized",
+                StatusCode::UNAUTHORIZED,
+            ));
+        }
+        if err.is_not_found() {
+            return Ok(warp::reply::with_status("Not Found", StatusCode::NOT_FOUND));
+        }
+        Ok(warp::reply::with_status(
+            "Bad Request",
+            StatusCode::BAD_REQUEST,
+        ))
+    }
+
+    /// Confirms `user` is a member of `timeline_id`, rejecting the request
+    /// otherwise. Yields both values back so the caller doesn't have to
+    /// re-authenticate just to recover the username.
+    pub async fn require_member(
+        timeline_id: i64,
+        user: String,
+        db: Arc<Mutex<Connection>>,
+    ) -> Result<(i64, String), warp::Rejection> {
+        let conn = db.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT 1 FROM memberships WHERE timeline_id = ? AND username = ?")
+            .unwrap();
+        stmt.bind((1, timeline_id)).unwrap();
+        stmt.bind((2, user.as_str())).unwrap();
+        if !matches!(stmt.next(), Ok(State::Row)) {
+            return Err(warp::reject::custom(AuthError));
+        }
+        Ok((timeline_id, user))
+    }
+
+    #[derive(Serialize)]
+    pub struct TimelineResponse {
+        pub id: i64,
+        pub name: String,
+    }
+
+    /// Creates a timeline and makes its creator its first member.
+    pub async fn create_timeline(
+        user: String,
+        name: String,
+        db: Arc<Mutex<Connection>>,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let conn = db.lock().unwrap();
+        let mut stmt = conn
+            .prepare("INSERT INTO timelines (name) VALUES (?);")
+            .unwrap();
+        stmt.bind((1, name.as_str())).unwrap();
+        if stmt.next().is_err() {
+            return Err(warp::reject::custom(AuthError));
+        }
+        let timeline_id = conn.last_insert_rowid();
+
+        let mut stmt = conn
+            .prepare("INSERT INTO memberships (timeline_id, username) VALUES (?, ?);")
+            .unwrap();
+        stmt.bind((1, timeline_id)).unwrap();
+        stmt.bind((2, user.as_str())).unwrap();
+        if stmt.next().is_err() {
+            return Err(warp::reject::custom(AuthError));
+        }
+
+        Ok(warp::reply::with_status(
+            warp::reply::json(&TimelineResponse {
+                id: timeline_id,
+                name,
+            }),
+            StatusCode::CREATED,
+        ))
+    }
+
+    #[derive(Serialize)]
+    pub struct InviteResponse {
+        pub token: String,
+        pub invitation: Invitation,
+    }
+
+    /// Records an invitation for `email` to join `timeline_id` and returns a
+    /// single-use token the invitee redeems via `accept_invitation`.
+    pub async fn invite(
+        timeline_id: i64,
+        inviter: String,
+        email: String,
+        db: Arc<Mutex<Connection>>,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let conn = db.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT 1 FROM memberships WHERE timeline_id = ? AND username = ?")
+            .unwrap();
+        stmt.bind((1, timeline_id)).unwrap();
+        stmt.bind((2, inviter.as_str())).unwrap();
+        if !matches!(stmt.next(), Ok(State::Row)) {
+            return Err(warp::reject::custom(AuthError));
+        }
+
+        let token: String = {
+            let mut rng = OsRng;
+            (0..32).map(|_| format!("{:02x}", rng.gen::<u8>())).collect()
+        };
+        let mut stmt = conn
+            .prepare(
+                "INSERT INTO invitations (token, email, timeline_id, invited_by, state)
+                 VALUES (?, ?, ?, ?, ?);",
+            )
+            .unwrap();
+        stmt.bind((1, token.as_str())).unwrap();
+        stmt.bind((2, email.as_str())).unwrap();
+        stmt.bind((3, timeline_id)).unwrap();
+        stmt.bind((4, inviter.as_str())).unwrap();
+        stmt.bind((5, InvitationState::Sent.as_str())).unwrap();
+        if stmt.next().is_err() {
+            return Err(warp::reject::custom(AuthError));
+        }
+        let id = conn.last_insert_rowid();
+
+        Ok(warp::reply::with_status(
+            warp::reply::json(&InviteResponse {
+                token,
+                invitation: Invitation {
+                    id,
+                    email,
+                    timeline_id,
+                    invited_by: inviter,
+                    state: InvitationState::Sent,
+                },
+            }),
+            StatusCode::CREATED,
+        ))
+    }
+
+    /// Redeems an invitation token: transitions it to `Accepted` and makes
+    /// the logged-in invitee a member of the invitation's timeline.
+    pub async fn accept_invitation(
+        token: String,
+        user: String,
+        db: Arc<Mutex<Connection>>,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let conn = db.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT timeline_id, state FROM invitations WHERE token = ?")
+            .unwrap();
+        stmt.bind((1, token.as_str())).unwrap();
+        if !matches!(stmt.next(), Ok(State::Row)) {
+            return Err(warp::reject::not_found());
+        }
+        let timeline_id = stmt.read::<i64, _>("timeline_id").unwrap();
+        let state = InvitationState::from_str(&stmt.read::<String, _>("state").unwrap());
+        if state != InvitationState::Sent {
+            return Err(warp::reject::custom(AuthError));
+        }
+
+        let mut stmt = conn
+            .prepare("UPDATE invitations SET state = ? WHERE token = ?")
+            .unwrap();
+        stmt.bind((1, InvitationState::Accepted.as_str())).unwrap();
+        stmt.bind((2, token.as_str())).unwrap();
+        if stmt.next().is_err() {
+            return Err(warp::reject::custom(AuthError));
+        }
+
+        let mut stmt = conn
+            .prepare("INSERT INTO memberships (timeline_id, username) VALUES (?, ?);")
+            .unwrap();
+        stmt.bind((1, timeline_id)).unwrap();
+        stmt.bind((2, user.as_str())).unwrap();
+        if stmt.next().is_err() {
+            return Err(warp::reject::custom(AuthError));
+        }
+
+        Ok(warp::reply::with_status(warp::reply(), StatusCode::OK))
+    }
+
+    /// Inserts `twit`, fans it out over SSE/ActivityPub/Nostr when it is on
+    /// the public default timeline (twits on other timelines are only
+    /// stored), and returns the row as stored (with its assigned
+    /// `id`/`created_at`). Shared by every
+    /// entry point that creates a twit, whether posted by a session-
+    /// authenticated user or a Micropub client.
+    pub async fn record_twit(
+        twit: Twit,
+        db: &Arc<Mutex<Connection>>,
+        twit_tx: &tokio::sync::broadcast::Sender<Twit>,
+        nostr_relays: &crate::nostr_bridge::RelayConnections,
+    ) -> Twit {
+        let inserted = {
+            let conn = db.lock().unwrap();
+            let mut stmt = conn
+                .prepare("INSERT INTO twits (user, content, timeline_id) VALUES (?, ?, ?);")
+                .unwrap();
+            let _ = stmt.bind((1, twit.user.as_str()));
+            let _ = stmt.bind((2, twit.content.as_str())).unwrap();
+            let _ = stmt.bind((3, twit.timeline_id)).unwrap();
+            let result = stmt.next().unwrap();
+            println!("{:?}", result);
+
+            let id = conn.last_insert_rowid();
+            let mut stmt = conn.prepare("SELECT * FROM twits WHERE id = ?").unwrap();
+            stmt.bind((1, id)).unwrap();
+            stmt.next().unwrap();
+            Twit {
+                id: Some(stmt.read::<i64, _>("id").unwrap()),
+                user: stmt.read::<String, _>("user").unwrap(),
+                content: stmt.read::<String, _>("content").unwrap(),
+                created_at: Some(stmt.read::<i64, _>("createdAt").unwrap()),
+                timeline_id: stmt.read::<i64, _>("timeline_id").unwrap(),
+            }
+        };
+
+        // Only the default timeline is public: twits posted to a private,
+        // invitation-only timeline must not leak to the SSE stream, remote
+        // ActivityPub followers, or public Nostr relays.
+        if inserted.timeline_id == DEFAULT_TIMELINE_ID {
+            // Best-effort: no one may be subscribed to the stream right now.
+            let _ = twit_tx.send(inserted.clone());
+
+            deliver_to_followers(db, &inserted);
+            publish_to_nostr(db, nostr_relays, &inserted).await;
+        }
+
+        inserted
+    }
+
     pub async fn create_twit(
         twit: Twit,
         db: Arc<Mutex<Connection>>,
+        twit_tx: tokio::sync::broadcast::Sender<Twit>,
+        nostr_relays: crate::nostr_bridge::RelayConnections,
     ) -> Result<impl warp::Reply, Infallible> {
+        let inserted = record_twit(twit, &db, &twit_tx, &nostr_relays).await;
+        Ok(warp::reply::json(&inserted))
+    }
+
+    /// Re-broadcasts the twit as a Nostr kind-1 text note under the
+    /// author's own keys, recording the published event id.
+    async fn publish_to_nostr(
+        db: &Arc<Mutex<Connection>>,
+        nostr_relays: &crate::nostr_bridge::RelayConnections,
+        twit: &Twit,
+    ) {
+        let secret_hex = {
+            let conn = db.lock().unwrap();
+            let mut stmt = conn
+                .prepare("SELECT nostr_secret_key FROM users WHERE username = ?")
+                .unwrap();
+            stmt.bind((1, twit.user.as_str())).unwrap();
+            if !matches!(stmt.next(), Ok(State::Row)) {
+                return;
+            }
+            stmt.read::<String, _>("nostr_secret_key").unwrap()
+        };
+        let Ok(keys) = nostr::Keys::parse(&secret_hex) else {
+            return;
+        };
+
+        let event_id = crate::nostr_bridge::publish(nostr_relays, &keys, &twit.content).await;
+
+        let conn = db.lock().unwrap();
+        let mut stmt = conn
+            .prepare("INSERT INTO nostr_events (username, event_id) VALUES (?, ?);")
+            .unwrap();
+        stmt.bind((1, twit.user.as_str())).unwrap();
+        stmt.bind((2, event_id.as_str())).unwrap();
+        let _ = stmt.next();
+    }
+
+    /// Fans the newly created twit out to every follower's inbox as a signed
+    /// `Create`/`Note` activity. Runs on its own tasks so a slow or
+    /// unreachable follower can't hold up the response to the poster.
+    fn deliver_to_followers(db: &Arc<Mutex<Connection>>, twit: &Twit) {
+        let private_key_pem = {
+            let conn = db.lock().unwrap();
+            let mut stmt = conn
+                .prepare("SELECT private_key_pem FROM users WHERE username = ?")
+                .unwrap();
+            stmt.bind((1, twit.user.as_str())).unwrap();
+            if !matches!(stmt.next(), Ok(State::Row)) {
+                return;
+            }
+            stmt.read::<String, _>("private_key_pem").unwrap()
+        };
+        let key_id = format!("{}#main-key", crate::federation::actor_id(&twit.user));
+        let activity = crate::federation::note_activity(&twit.user, twit);
+
+        let conn = db.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT follower_inbox FROM followers WHERE username = ?")
+            .unwrap();
+        stmt.bind((1, twit.user.as_str())).unwrap();
+        while let Ok(State::Row) = stmt.next() {
+            let inbox = stmt.read::<String, _>("follower_inbox").unwrap();
+            tokio::spawn(crate::federation::deliver(
+                private_key_pem.clone(),
+                key_id.clone(),
+                inbox,
+                activity.clone(),
+            ));
+        }
+    }
+
+    /// Adapts a broadcast receiver of newly created twits into an SSE event
+    /// stream, skipping any messages a slow subscriber missed.
+    pub fn twit_events(
+        rx: tokio::sync::broadcast::Receiver<Twit>,
+    ) -> impl futures::Stream<Item = Result<warp::sse::Event, std::convert::Infallible>> {
+        use tokio_stream::{wrappers::BroadcastStream, StreamExt as _};
+
+        BroadcastStream::new(rx).filter_map(|twit| {
+            twit.ok().map(|twit| {
+                Ok(warp::sse::Event::default().json_data(&twit).unwrap())
+            })
+        })
+    }
+
+    fn user_key(db: &Arc<Mutex<Connection>>, username: &str, column: &str) -> Option<String> {
+        let conn = db.lock().unwrap();
+        let mut stmt = conn
+            .prepare(format!("SELECT {} FROM users WHERE username = ?", column))
+            .unwrap();
+        stmt.bind((1, username)).unwrap();
+        if !matches!(stmt.next(), Ok(State::Row)) {
+            return None;
+        }
+        Some(stmt.read::<String, _>(column).unwrap())
+    }
+
+    pub async fn actor(
+        username: String,
+        db: Arc<Mutex<Connection>>,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let Some(public_key_pem) = user_key(&db, &username, "public_key_pem") else {
+            return Err(warp::reject::not_found());
+        };
+        let id = crate::federation::actor_id(&username);
+        Ok(warp::reply::json(&serde_json::json!({
+            "@context": ["https://www.w3.org/ns/activitystreams", "https://w3id.org/security/v1"],
+            "id": id,
+            "type": "Person",
+            "preferredUsername": username,
+            "inbox": format!("{}/inbox", id),
+            "outbox": format!("{}/outbox", id),
+            "publicKey": {
+                "id": format!("{}#main-key", id),
+                "owner": id,
+                "publicKeyPem": public_key_pem,
+            },
+        })))
+    }
+
+    pub async fn outbox(
+        username: String,
+        db: Arc<Mutex<Connection>>,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        if user_key(&db, &username, "public_key_pem").is_none() {
+            return Err(warp::reject::not_found());
+        }
+        let twits = {
+            let conn = db.lock().unwrap();
+            let mut stmt = conn
+                .prepare(
+                    "SELECT * FROM twits WHERE user = ? AND timeline_id = ? ORDER BY id DESC LIMIT ?",
+                )
+                .unwrap();
+            stmt.bind((1, username.as_str())).unwrap();
+            stmt.bind((2, DEFAULT_TIMELINE_ID)).unwrap();
+            stmt.bind((3, MAX_PAGE_LIMIT)).unwrap();
+            let mut twits = Vec::new();
+            while let Ok(State::Row) = stmt.next() {
+                twits.push(Twit {
+                    id: Some(stmt.read::<i64, _>("id").unwrap()),
+                    user: stmt.read::<String, _>("user").unwrap(),
+                    content: stmt.read::<String, _>("content").unwrap(),
+                    created_at: Some(stmt.read::<i64, _>("createdAt").unwrap()),
+                    timeline_id: stmt.read::<i64, _>("timeline_id").unwrap(),
+                });
+            }
+            twits
+        };
+        let items: Vec<_> = twits
+            .iter()
+            .map(|t| crate::federation::note_activity(&username, t))
+            .collect();
+
+        Ok(warp::reply::json(&serde_json::json!({
+            "@context": "https://www.w3.org/ns/activitystreams",
+            "id": format!("{}/outbox", crate::federation::actor_id(&username)),
+            "type": "OrderedCollection",
+            "totalItems": items.len(),
+            "orderedItems": items,
+        })))
+    }
+
+    #[derive(Deserialize)]
+    pub struct InboxActivity {
+        #[serde(rename = "type")]
+        pub activity_type: String,
+        pub actor: String,
+    }
+
+    pub async fn inbox(
+        username: String,
+        signature: Option<String>,
+        host: Option<String>,
+        date: Option<String>,
+        digest: Option<String>,
+        body: bytes::Bytes,
+        db: Arc<Mutex<Connection>>,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        if user_key(&db, &username, "public_key_pem").is_none() {
+            return Err(warp::reject::not_found());
+        }
+        if !crate::federation::digest_matches(&body, digest.as_deref()) {
+            return Err(warp::reject::custom(AuthError));
+        }
+        let Ok(activity) = serde_json::from_slice::<InboxActivity>(&body) else {
+            return Err(warp::reject::custom(AuthError));
+        };
+        if activity.activity_type != "Follow" {
+            return Err(warp::reject::custom(AuthError));
+        }
+        if !crate::federation::verify_request(&username, &activity.actor, signature, host, date, digest)
+            .await
+        {
+            return Err(warp::reject::custom(AuthError));
+        }
+
+        let follower_inbox = format!("{}/inbox", activity.actor);
+        {
+            let conn = db.lock().unwrap();
+            let mut stmt = conn
+                .prepare("INSERT INTO followers (username, follower_inbox) VALUES (?, ?);")
+                .unwrap();
+            stmt.bind((1, username.as_str())).unwrap();
+            stmt.bind((2, follower_inbox.as_str())).unwrap();
+            let _ = stmt.next();
+        }
+
+        Ok(warp::reply::json(&serde_json::json!({
+            "@context": "https://www.w3.org/ns/activitystreams",
+            "type": "Accept",
+            "actor": crate::federation::actor_id(&username),
+            "object": { "type": "Follow", "actor": activity.actor },
+        })))
+    }
+
+    #[derive(Serialize)]
+    pub struct NostrInfo {
+        pub npub: String,
+        pub event_ids: Vec<String>,
+    }
+
+    pub async fn nostr_info(
+        username: String,
+        db: Arc<Mutex<Connection>>,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        let Some(secret_hex) = user_key(&db, &username, "nostr_secret_key") else {
+            return Err(warp::reject::not_found());
+        };
+        let Ok(keys) = nostr::Keys::parse(&secret_hex) else {
+            return Err(warp::reject::custom(AuthError));
+        };
+        let npub = keys.public_key().to_bech32().unwrap();
+
         let conn = db.lock().unwrap();
         let mut stmt = conn
-            .prepare("INSERT INTO twits (user, content) VALUES (?,?);")
+            .prepare("SELECT event_id FROM nostr_events WHERE username = ?")
             .unwrap();
-        let _ = stmt.bind((1, twit.user.as_str()));
-        let _ = stmt.bind((2, twit.content.as_str())).unwrap();
-        let result = stmt.next().unwrap();
-        println!("{:?}", result);
-        Ok(warp::reply::json(&twit))
+        stmt.bind((1, username.as_str())).unwrap();
+        let mut event_ids = Vec::new();
+        while let Ok(State::Row) = stmt.next() {
+            event_ids.push(stmt.read::<String, _>("event_id").unwrap());
+        }
+
+        Ok(warp::reply::json(&NostrInfo { npub, event_ids }))
+    }
+
+    pub async fn micropub_post(
+        authorization: String,
+        form: crate::filters::MicropubForm,
+        db: Arc<Mutex<Connection>>,
+        twit_tx: tokio::sync::broadcast::Sender<Twit>,
+        nostr_relays: crate::nostr_bridge::RelayConnections,
+    ) -> Result<impl warp::Reply, warp::Rejection> {
+        if form.h != "entry" {
+            return Err(warp::reject::custom(AuthError));
+        }
+        let token = authorization
+            .strip_prefix("Bearer ")
+            .unwrap_or(authorization.as_str());
+        let Some(token_info) = crate::indieauth::verify(token).await else {
+            return Err(warp::reject::custom(AuthError));
+        };
+        // Micropub identities are gated by the same membership check as
+        // session posts: a verified `me` still needs a membership row on the
+        // default timeline before it can post into it.
+        require_member(DEFAULT_TIMELINE_ID, token_info.me.clone(), db.clone()).await?;
+
+        let twit = Twit {
+            id: None,
+            user: token_info.me,
+            content: form.content,
+            created_at: None,
+            timeline_id: DEFAULT_TIMELINE_ID,
+        };
+        let inserted = record_twit(twit, &db, &twit_tx, &nostr_relays).await;
+
+        Ok(warp::reply::with_status(
+            warp::reply::json(&inserted),
+            StatusCode::CREATED,
+        ))
     }
 
     pub async fn index_html(hbs: Arc<Handlebars<'static>>) -> Result<impl warp::Reply, Infallible> {
@@ -166,6 +1180,300 @@ mod handlers {
     }
 }
 
+/// ActivityPub federation: signing/verifying HTTP Signatures and delivering
+/// activities to followers. Kept separate from `handlers` because it talks
+/// to remote servers rather than to our own DB/templates.
+mod federation {
+    use super::Twit;
+    use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey};
+    use rsa::{Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey};
+    use serde_json::{json, Value};
+    use sha2::{Digest, Sha256};
+
+    /// Public base URL this instance is reachable at: `TWITS_BASE_URL` if
+    /// set, otherwise the local dev default. A real deployment behind a
+    /// domain name must set this, or the actor ids/inboxes it advertises to
+    /// remote servers will point nowhere.
+    pub fn base_url() -> String {
+        std::env::var("TWITS_BASE_URL").unwrap_or_else(|_| "http://localhost:3030".to_string())
+    }
+
+    pub fn actor_id(username: &str) -> String {
+        format!("{}/users/{}", base_url(), username)
+    }
+
+    /// Generates a fresh RSA keypair for a newly registered actor, PEM-encoded
+    /// for storage alongside the user row.
+    pub fn generate_keypair() -> (String, String) {
+        let mut rng = rand::thread_rng();
+        let private_key = RsaPrivateKey::new(&mut rng, 2048).expect("RSA keygen");
+        let public_key = RsaPublicKey::from(&private_key);
+        let private_pem = private_key
+            .to_pkcs8_pem(Default::default())
+            .unwrap()
+            .to_string();
+        let public_pem = public_key.to_public_key_pem(Default::default()).unwrap();
+        (private_pem, public_pem)
+    }
+
+    fn sign(private_pem: &str, signing_string: &str) -> String {
+        let private_key = RsaPrivateKey::from_pkcs8_pem(private_pem).unwrap();
+        let digest = Sha256::digest(signing_string.as_bytes());
+        let signature = private_key
+            .sign(Pkcs1v15Sign::new::<Sha256>(), &digest)
+            .unwrap();
+        base64::encode(signature)
+    }
+
+    /// Verifies a `Signature` header's `(request-target)`/`host`/`date`/`digest`
+    /// string against the actor's public key.
+    pub fn verify(public_pem: &str, signing_string: &str, signature_b64: &str) -> bool {
+        let Ok(public_key) = RsaPublicKey::from_public_key_pem(public_pem) else {
+            return false;
+        };
+        let Ok(signature) = base64::decode(signature_b64) else {
+            return false;
+        };
+        let digest = Sha256::digest(signing_string.as_bytes());
+        public_key
+            .verify(Pkcs1v15Sign::new::<Sha256>(), &digest, &signature)
+            .is_ok()
+    }
+
+    /// Recomputes `SHA-256=<base64>` over the raw request body and checks it
+    /// against the `Digest` header, so a signature can't be replayed against
+    /// a swapped-out payload.
+    pub fn digest_matches(body: &[u8], digest_header: Option<&str>) -> bool {
+        let Some(digest_header) = digest_header else {
+            return false;
+        };
+        let expected = format!("SHA-256={}", base64::encode(Sha256::digest(body)));
+        digest_header == expected
+    }
+
+    /// Pulls the `signature="..."` field out of a `Signature` header value
+    /// of the form `keyId="...",algorithm="...",headers="...",signature="..."`.
+    fn signature_param(header: &str) -> Option<String> {
+        header.split(',').find_map(|part| {
+            let (key, value) = part.split_once('=')?;
+            (key.trim() == "signature").then(|| value.trim_matches('"').to_string())
+        })
+    }
+
+    /// Verifies an inbound inbox POST's `Signature` header against the
+    /// sending actor's published public key, fetched fresh from `actor_url`.
+    /// Returns `false` if any header is missing or the signature doesn't
+    /// check out, so the caller can reject the request before trusting it.
+    pub async fn verify_request(
+        username: &str,
+        actor_url: &str,
+        signature: Option<String>,
+        host: Option<String>,
+        date: Option<String>,
+        digest: Option<String>,
+    ) -> bool {
+        let (Some(signature), Some(host), Some(date), Some(digest)) =
+            (signature, host, date, digest)
+        else {
+            return false;
+        };
+        let Some(signature_b64) = signature_param(&signature) else {
+            return false;
+        };
+
+        let actor: Value = match reqwest::get(actor_url).await {
+            Ok(resp) => match resp.json().await {
+                Ok(actor) => actor,
+                Err(_) => return false,
+            },
+            Err(_) => return false,
+        };
+        let Some(public_key_pem) = actor["publicKey"]["publicKeyPem"].as_str() else {
+            return false;
+        };
+
+        let path = format!("/users/{}/inbox", username);
+        let signing_string =
+            format!("(request-target): post {}\nhost: {}\ndate: {}\ndigest: {}", path, host, date, digest);
+
+        verify(public_key_pem, &signing_string, &signature_b64)
+    }
+
+    pub fn note_activity(username: &str, twit: &Twit) -> Value {
+        let actor = actor_id(username);
+        let object_id = format!("{}/twits/{}", actor, twit.id.unwrap_or_default());
+        json!({
+            "@context": "https://www.w3.org/ns/activitystreams",
+            "id": format!("{}/activity", object_id),
+            "type": "Create",
+            "actor": actor,
+            "object": {
+                "id": object_id,
+                "type": "Note",
+                "attributedTo": actor,
+                "content": twit.content,
+            },
+        })
+    }
+
+    /// Signs and POSTs `activity` to `inbox` per the HTTP Signatures draft:
+    /// the signature covers `(request-target)`, `host`, `date` and `digest`.
+    pub async fn deliver(private_pem: String, key_id: String, inbox: String, activity: Value) {
+        let body = activity.to_string();
+        let digest = format!(
+            "SHA-256={}",
+            base64::encode(Sha256::digest(body.as_bytes()))
+        );
+        let Ok(url) = reqwest::Url::parse(&inbox) else {
+            return;
+        };
+        let host = url.host_str().unwrap_or_default().to_string();
+        let date = httpdate::fmt_http_date(std::time::SystemTime::now());
+
+        let signing_string = format!(
+            "(request-target): post {}\nhost: {}\ndate: {}\ndigest: {}",
+            url.path(),
+            host,
+            date,
+            digest
+        );
+        let signature = sign(&private_pem, &signing_string);
+        let signature_header = format!(
+            "keyId=\"{}\",algorithm=\"rsa-sha256\",headers=\"(request-target) host date digest\",signature=\"{}\"",
+            key_id, signature
+        );
+
+        let client = reqwest::Client::new();
+        let _ = client
+            .post(inbox)
+            .header("Host", host)
+            .header("Date", date)
+            .header("Digest", digest)
+            .header("Signature", signature_header)
+            .header("Content-Type", "application/activity+json")
+            .body(body)
+            .send()
+            .await;
+    }
+}
+
+/// Optional bridge that mirrors each twit as a signed Nostr kind-1 text note,
+/// broadcast to a configurable set of relays. A connection per relay is kept
+/// open in `TwitterServer::nostr_relays` and re-established lazily if it
+/// dropped.
+mod nostr_bridge {
+    use futures_util::SinkExt;
+    use nostr::{EventBuilder, Keys};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::sync::Mutex as AsyncMutex;
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+    /// Relays every created twit is broadcast to: a comma-separated
+    /// `TWITS_NOSTR_RELAYS` env var, or a couple of public relays by default
+    /// so a fresh checkout has somewhere to publish to.
+    pub fn relay_urls() -> Vec<String> {
+        match std::env::var("TWITS_NOSTR_RELAYS") {
+            Ok(urls) => urls
+                .split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(String::from)
+                .collect(),
+            Err(_) => vec!["wss://relay.damus.io".to_string(), "wss://nos.lol".to_string()],
+        }
+    }
+
+    type RelaySocket = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+    pub type RelayConnections = Arc<AsyncMutex<HashMap<String, RelaySocket>>>;
+
+    pub fn new_connections() -> RelayConnections {
+        Arc::new(AsyncMutex::new(HashMap::new()))
+    }
+
+    pub fn generate_keys() -> Keys {
+        Keys::generate()
+    }
+
+    /// Builds and signs a kind-1 text note for `content`, broadcasts it to
+    /// every configured relay (reconnecting lazily), and returns the event id
+    /// as a hex string regardless of whether any relay accepted it.
+    pub async fn publish(relays: &RelayConnections, keys: &Keys, content: &str) -> String {
+        let event = EventBuilder::text_note(content, []).to_event(keys).unwrap();
+        let event_id = event.id.to_hex();
+        let payload = serde_json::json!(["EVENT", event]).to_string();
+
+        let mut conns = relays.lock().await;
+        for url in relay_urls() {
+            if !conns.contains_key(&url) {
+                if let Ok((socket, _)) = tokio_tungstenite::connect_async(url.as_str()).await {
+                    conns.insert(url.clone(), socket);
+                } else {
+                    continue;
+                }
+            }
+            if let Some(socket) = conns.get_mut(&url) {
+                if socket.send(Message::Text(payload.clone())).await.is_err() {
+                    // Drop the stale connection; the next publish reconnects.
+                    conns.remove(&url);
+                }
+            }
+        }
+
+        event_id
+    }
+}
+
+/// IndieAuth token verification for the Micropub endpoint. Posting identity
+/// comes from whatever `me`/`scope` the configured token endpoint vouches
+/// for, rather than from our own `users` table.
+///
+/// This instance trusts exactly one token endpoint (below), not each
+/// caller's own domain — real IndieAuth discovers a per-`me` token endpoint
+/// via `rel="token_endpoint"` on their homepage, so this only accepts tokens
+/// issued by whoever `TWITS_TOKEN_ENDPOINT` points at, not "any indieweb
+/// identity" in general.
+mod indieauth {
+    use serde::Deserialize;
+
+    /// Token endpoint that verifies Micropub bearer tokens: `TWITS_TOKEN_ENDPOINT`
+    /// if set, otherwise tokens.indieauth.com.
+    pub fn token_endpoint() -> String {
+        std::env::var("TWITS_TOKEN_ENDPOINT")
+            .unwrap_or_else(|_| "https://tokens.indieauth.com/token".to_string())
+    }
+
+    #[derive(Deserialize)]
+    pub struct TokenInfo {
+        pub me: String,
+        #[serde(default)]
+        pub scope: String,
+    }
+
+    /// Calls the token endpoint with the bearer token and returns the
+    /// verified identity, or `None` if the token is invalid or its scope
+    /// doesn't include `create`.
+    pub async fn verify(token: &str) -> Option<TokenInfo> {
+        let client = reqwest::Client::new();
+        let resp = client
+            .get(token_endpoint())
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Accept", "application/json")
+            .send()
+            .await
+            .ok()?;
+        if !resp.status().is_success() {
+            return None;
+        }
+        let info: TokenInfo = resp.json().await.ok()?;
+        if !info.scope.split_whitespace().any(|s| s == "create") {
+            return None;
+        }
+        Some(info)
+    }
+}
+
 impl TwitterServer {
     fn new() -> TwitterServer {
         // DB Setup
@@ -173,12 +1481,56 @@ impl TwitterServer {
         let twit_table = "
     CREATE TABLE twits (
         id INTEGER PRIMARY KEY AUTOINCREMENT,
-        user TEXT NOT NULL, 
-        content TEXT NOT NULl, 
-        createdAt TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        user TEXT NOT NULL,
+        content TEXT NOT NULl,
+        createdAt TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+        timeline_id INTEGER NOT NULL
+    );
+
+    CREATE TABLE users (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        username TEXT NOT NULL UNIQUE,
+        password_hash TEXT NOT NULL,
+        public_key_pem TEXT NOT NULL,
+        private_key_pem TEXT NOT NULL,
+        nostr_secret_key TEXT NOT NULL
+    );
+
+    CREATE TABLE followers (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        username TEXT NOT NULL,
+        follower_inbox TEXT NOT NULL
     );
 
-    INSERT INTO twits (user, content) VALUES ('Bob', 'First twit');
+    CREATE TABLE nostr_events (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        username TEXT NOT NULL,
+        event_id TEXT NOT NULL
+    );
+
+    CREATE TABLE timelines (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL
+    );
+
+    CREATE TABLE memberships (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        timeline_id INTEGER NOT NULL,
+        username TEXT NOT NULL
+    );
+
+    CREATE TABLE invitations (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        token TEXT NOT NULL UNIQUE,
+        email TEXT NOT NULL,
+        timeline_id INTEGER NOT NULL,
+        invited_by TEXT NOT NULL,
+        state TEXT NOT NULL
+    );
+
+    INSERT INTO timelines (id, name) VALUES (1, 'Default');
+    INSERT INTO memberships (timeline_id, username) VALUES (1, 'Bob');
+    INSERT INTO twits (user, content, timeline_id) VALUES ('Bob', 'First twit', 1);
     ";
         conn.execute(twit_table).unwrap();
 
@@ -192,9 +1544,14 @@ impl TwitterServer {
             .unwrap();
         let hbs = Arc::new(hbs);
 
+        let (twit_tx, _) = tokio::sync::broadcast::channel(16);
+
         TwitterServer {
             db: Arc::new(Mutex::new(conn)),
             hbs,
+            twit_tx,
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            nostr_relays: nostr_bridge::new_connections(),
         }
     }
 }
@@ -202,6 +1559,13 @@ impl TwitterServer {
 #[tokio::main]
 async fn main() {
     let srv = TwitterServer::new();
-    let routes = filters::twits(srv.db.clone()).or(filters::html(srv.db.clone(), srv.hbs));
+    let routes = filters::twits(
+        srv.db.clone(),
+        srv.twit_tx.clone(),
+        srv.sessions.clone(),
+        srv.nostr_relays.clone(),
+    )
+    .or(filters::html(srv.db.clone(), srv.hbs))
+    .recover(handlers::handle_rejection);
     warp::serve(routes).run(([127, 0, 0, 1], 3030)).await;
 }